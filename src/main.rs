@@ -1,5 +1,12 @@
-use std::{cmp, convert::TryInto, error::Error, u64};
-
+use std::{
+  cmp,
+  cmp::Reverse,
+  collections::{BinaryHeap, HashSet},
+  error::Error,
+  str::FromStr,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -10,41 +17,190 @@ struct Config {
   #[structopt(name = "MAX", long = "max", default_value = "99")]
   max: u64,
   #[structopt(name = "RANGE", long = "range", default_value = "2")]
-  range: u64,
+  range: Vec<u64>,
   #[structopt(name = "COMBINATION", long = "combination")]
   combination: Vec<u64>,
   #[structopt(name = "CSV", long = "csv")]
   csv: bool,
+  #[structopt(name = "JSON", long = "json")]
+  json: bool,
+  #[structopt(name = "TOP", long = "top")]
+  top: Option<u64>,
+  #[structopt(name = "METRIC", long = "metric", default_value = "modular")]
+  metric: Metric,
+  #[structopt(name = "SAMPLE", long = "sample")]
+  sample: Option<u64>,
+  #[structopt(name = "SEED", long = "seed", default_value = "0")]
+  seed: u64,
+}
+
+// `modular` treats the dial as a wheel, so the ends are adjacent; `linear`
+// treats it as a strip, so being near an end isn't "close" to the other.
+#[derive(Debug, Clone, Copy)]
+enum Metric {
+  Modular,
+  Linear,
+}
+
+impl FromStr for Metric {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "modular" => Ok(Metric::Modular),
+      "linear" => Ok(Metric::Linear),
+      _ => Err(format!("metric must be `modular` or `linear`, not `{}`", s)),
+    }
+  }
+}
+
+impl Metric {
+  fn distance(self, a: u64, b: u64, modulus: u64) -> u64 {
+    match self {
+      Metric::Modular => modular_distance(a, b, modulus),
+      Metric::Linear => a.abs_diff(b),
+    }
+  }
+}
+
+enum OutputFormat {
+  Csv,
+  Json,
+  Unstructured,
 }
 
 fn modular_distance(a: u64, b: u64, modulus: u64) -> u64 {
   cmp::min((a + modulus - b) % modulus, (b + modulus - a) % modulus)
 }
 
+// Per dial: the values it can reach paired with their distance from the
+// target digit, sorted nearest-first.
+type Candidates = Vec<Vec<(u64, u64)>>;
+
 impl Config {
-  fn guesses(&self) -> Result<Vec<Vec<u64>>, Box<dyn Error>> {
-    let base = self.range * 2 + 1;
+  // `--range` may be given once, applying to every dial, or once per
+  // combination digit, letting each dial carry its own uncertainty.
+  fn ranges(&self) -> Result<Vec<u64>, Box<dyn Error>> {
+    match self.range.len() {
+      1 => Ok(vec![self.range[0]; self.combination.len()]),
+      n if n == self.combination.len() => Ok(self.range.clone()),
+      n => Err(
+        format!(
+          "`--range` given {} times, but must be given once, or once per combination digit ({})",
+          n,
+          self.combination.len()
+        )
+        .into(),
+      ),
+    }
+  }
+
+  fn candidates(&self) -> Result<Candidates, Box<dyn Error>> {
+    let ranges = self.ranges()?;
+
+    Ok(
+      self
+        .combination
+        .iter()
+        .zip(&ranges)
+        .map(|(&digit, &range)| self.dial_candidates(digit, range))
+        .collect(),
+    )
+  }
+
+  // The values a single dial can reach, paired with their true distance
+  // from `digit`, deduped by value and sorted nearest-first.
+  fn dial_candidates(&self, digit: u64, range: u64) -> Vec<(u64, u64)> {
+    let modulus = self.modulus();
+
+    let mut candidates: Vec<(u64, u64)> = match self.metric {
+      Metric::Modular => {
+        // Past `modulus/2` away, a modular dial is walking back towards
+        // `digit` from the other side, so larger offsets only revisit
+        // values already reached. Clamping here keeps every candidate's
+        // cost equal to its true wraparound distance and keeps each value
+        // in the list exactly once.
+        let half = (modulus / 2) as i64;
+        let range = cmp::min(range as i64, half);
+
+        let mut seen = HashSet::new();
+
+        (-range..=range)
+          .filter_map(|offset| {
+            let value = ((digit - self.min) as i64 + offset).rem_euclid(modulus as i64) as u64 + self.min;
+            seen.insert(value).then(|| {
+              let cost = modular_distance(value - self.min, digit - self.min, modulus);
+              (value, cost)
+            })
+          })
+          .collect()
+      }
+      Metric::Linear => {
+        let range = range as i64;
+
+        (-range..=range)
+          .filter_map(|offset| {
+            let value = digit as i64 + offset;
+            if value >= self.min as i64 && value <= self.max as i64 {
+              Some((value as u64, offset.unsigned_abs()))
+            } else {
+              None
+            }
+          })
+          .collect()
+      }
+    };
 
-    let numbers: u32 = self.combination.len().try_into()?;
+    candidates.sort_by_key(|&(_, cost)| cost);
 
-    let last = base.pow(numbers);
+    candidates
+  }
 
-    let mut guesses = Vec::new();
+  // Yield guesses in strictly increasing order of total error, without ever
+  // materializing the full `(2*range+1)^numbers` search space. Each dial's
+  // candidates are already sorted by cost, so this is a k-smallest-sums
+  // search over those sorted lists: a min-heap of index tuples, seeded at
+  // all-zeroes, where popping a tuple and pushing its per-dial successors
+  // visits every tuple in cost order exactly once.
+  fn guesses(&self) -> Result<Box<dyn Iterator<Item = Vec<u64>>>, Box<dyn Error>> {
+    let candidates = self.candidates()?;
+    let numbers = candidates.len();
+    let top = self.top;
+
+    let cost_of = |candidates: &Candidates, indices: &[usize]| -> u64 {
+      indices.iter().enumerate().map(|(i, &k)| candidates[i][k].1).sum()
+    };
+
+    let start = vec![0; numbers];
+    let start_cost = cost_of(&candidates, &start);
+
+    let mut seen = HashSet::new();
+    seen.insert(start.clone());
 
-    for mut delta in 0..last {
-      let mut guess = self.combination.clone();
-      for n in &mut guess {
-        let dn = delta % base;
-        delta = delta / base;
-        let offset = *n - self.min;
-        *n = (offset + self.modulus() + dn - self.range) % self.modulus() + self.min;
+    let mut heap = BinaryHeap::new();
+    heap.push(Reverse((start_cost, start)));
+
+    let mut emitted = 0;
+
+    Ok(Box::new(std::iter::from_fn(move || {
+      if top.is_some_and(|top| emitted >= top) {
+        return None;
       }
-      guesses.push(guess);
-    }
 
-    guesses.sort_by_key(|guess| self.errors(guess));
+      let Reverse((_, indices)) = heap.pop()?;
 
-    Ok(guesses)
+      for i in 0..numbers {
+        let mut next = indices.clone();
+        next[i] += 1;
+        if next[i] < candidates[i].len() && seen.insert(next.clone()) {
+          heap.push(Reverse((cost_of(&candidates, &next), next)));
+        }
+      }
+
+      emitted += 1;
+
+      Some(indices.iter().enumerate().map(|(i, &k)| candidates[i][k].0).collect())
+    })))
   }
 
   fn modulus(&self) -> u64 {
@@ -55,46 +211,102 @@ impl Config {
     guess
       .iter()
       .zip(&self.combination)
-      .map(|(g, c)| modular_distance(*g - self.min, *c - self.min, self.modulus()))
+      .map(|(g, c)| self.metric.distance(*g - self.min, *c - self.min, self.modulus()))
       .sum::<u64>()
   }
 
-  fn run(self) -> Result<(), Box<dyn Error>> {
-    let guesses = self.guesses()?;
+  // Draw `count` distinct guesses uniformly from the reachable offset
+  // hypercube, for search spaces too large for `guesses` to enumerate.
+  // Reuses `candidates` so a dial's reachable values respect the chosen
+  // `--metric` exactly as the exhaustive search does.
+  fn sample(&self, count: u64) -> Result<Vec<Vec<u64>>, Box<dyn Error>> {
+    let candidates = self.candidates()?;
 
-    if self.csv {
-      self.print_csv(&guesses);
-    } else {
-      self.print_unstructured(&guesses);
+    let mut rng = StdRng::seed_from_u64(self.seed);
+    let mut seen = HashSet::new();
+    let mut guesses = Vec::new();
+
+    // Bound retries so a `--sample` larger than the reachable space still
+    // terminates once every distinct guess has been found.
+    let max_attempts = count.saturating_mul(100).max(10_000);
+
+    for _ in 0..max_attempts {
+      if guesses.len() as u64 >= count {
+        break;
+      }
+
+      let guess = candidates
+        .iter()
+        .map(|dial| dial[rng.gen_range(0, dial.len())].0)
+        .collect::<Vec<u64>>();
+
+      if seen.insert(guess.clone()) {
+        guesses.push(guess);
+      }
     }
 
-    Ok(())
+    guesses.sort_by_key(|guess| self.errors(guess));
+
+    Ok(guesses)
+  }
+
+  fn output_format(&self) -> OutputFormat {
+    if self.json {
+      OutputFormat::Json
+    } else if self.csv {
+      OutputFormat::Csv
+    } else {
+      OutputFormat::Unstructured
+    }
   }
 
-  fn print_csv(&self, guesses: &[Vec<u64>]) {
-    if guesses.is_empty() {
-      return;
+  fn run(self) -> Result<(), Box<dyn Error>> {
+    let guesses: Box<dyn Iterator<Item = Vec<u64>>> = match self.sample {
+      Some(count) => Box::new(self.sample(count)?.into_iter()),
+      None => self.guesses()?,
+    };
+
+    match self.output_format() {
+      OutputFormat::Csv => self.print_csv(guesses),
+      OutputFormat::Json => self.print_json(guesses),
+      OutputFormat::Unstructured => self.print_unstructured(guesses),
     }
 
-    let numbers = guesses[0].len();
+    Ok(())
+  }
 
+  fn print_csv(&self, guesses: impl Iterator<Item = Vec<u64>>) {
     print!("tried");
-    for i in 0..numbers {
+    for i in 0..self.combination.len() {
       print!(",number {}", i + 1);
     }
     print!(",errors");
     println!();
 
     for guess in guesses {
-      for n in guess.iter() {
+      for n in &guess {
         print!(",{}", n);
       }
-      print!(",{}", self.errors(guess));
+      print!(",{}", self.errors(&guess));
       println!();
     }
   }
 
-  fn print_unstructured(&self, guesses: &[Vec<u64>]) {
+  // Newline-delimited JSON, so a guess can be printed as soon as it's
+  // produced instead of buffering the whole (potentially unbounded) stream
+  // into a single top-level array.
+  fn print_json(&self, guesses: impl Iterator<Item = Vec<u64>>) {
+    for guess in guesses {
+      println!("{}", self.format_json(&guess));
+    }
+  }
+
+  fn format_json(&self, guess: &[u64]) -> String {
+    let numbers = guess.iter().map(u64::to_string).collect::<Vec<String>>().join(",");
+    format!("{{\"numbers\":[{}],\"errors\":{}}}", numbers, self.errors(guess))
+  }
+
+  fn print_unstructured(&self, guesses: impl Iterator<Item = Vec<u64>>) {
     let mut errors = u64::MAX;
 
     let width = self.max.to_string().chars().count();
@@ -132,12 +344,17 @@ mod tests {
     let config = Config {
       min: 0,
       max: 99,
-      range: 0,
+      range: vec![0],
       csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Modular,
+      sample: None,
+      seed: 0,
       combination: combination.clone(),
     };
 
-    assert_eq!(config.guesses()?, &[combination]);
+    assert_eq!(config.guesses()?.collect::<Vec<Vec<u64>>>(), &[combination]);
 
     Ok(())
   }
@@ -149,12 +366,17 @@ mod tests {
     let config = Config {
       min: 0,
       max: 99,
-      range: 1,
+      range: vec![1],
       csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Modular,
+      sample: None,
+      seed: 0,
       combination: combination.clone(),
     };
 
-    assert_eq!(config.guesses()?.len(), 3);
+    assert_eq!(config.guesses()?.count(), 3);
 
     Ok(())
   }
@@ -166,16 +388,345 @@ mod tests {
     let config = Config {
       min: 0,
       max: 99,
-      range: 1,
+      range: vec![1],
       csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Modular,
+      sample: None,
+      seed: 0,
       combination: combination.clone(),
     };
 
-    assert_eq!(config.guesses()?.len(), 9);
+    assert_eq!(config.guesses()?.count(), 9);
+
+    Ok(())
+  }
+
+  #[test]
+  fn guesses_are_sorted_by_error() -> Result<(), Box<dyn Error>> {
+    let combination = vec![50, 50];
+
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![3],
+      csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Modular,
+      sample: None,
+      seed: 0,
+      combination,
+    };
+
+    let errors = config.guesses()?.map(|guess| config.errors(&guess)).collect::<Vec<u64>>();
+
+    let mut sorted = errors.clone();
+    sorted.sort();
+
+    assert_eq!(errors, sorted);
 
     Ok(())
   }
 
+  #[test]
+  fn top_limits_guess_count() -> Result<(), Box<dyn Error>> {
+    let combination = vec![50, 50];
+
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![3],
+      csv: false,
+      json: false,
+      top: Some(5),
+      metric: Metric::Modular,
+      sample: None,
+      seed: 0,
+      combination,
+    };
+
+    assert_eq!(config.guesses()?.count(), 5);
+
+    Ok(())
+  }
+
+  #[test]
+  fn per_dial_range() -> Result<(), Box<dyn Error>> {
+    let combination = vec![50, 50, 50];
+
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![1, 0, 2],
+      csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Modular,
+      sample: None,
+      seed: 0,
+      combination,
+    };
+
+    // (2*1+1) * (2*0+1) * (2*2+1) = 15
+    assert_eq!(config.guesses()?.count(), 15);
+
+    Ok(())
+  }
+
+  #[test]
+  fn per_dial_range_larger_than_one_dials_modulus() -> Result<(), Box<dyn Error>> {
+    // A dial the caller "has no idea about" gets a generous range, but a
+    // neighboring small-modulus dial (e.g. a 0-9 digit) must not blow up
+    // into duplicate or out-of-order candidates just because its `--range`
+    // exceeds half of its own modulus.
+    let combination = vec![20, 5];
+
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![60, 60],
+      csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Modular,
+      sample: None,
+      seed: 0,
+      combination,
+    };
+
+    let guesses = config.guesses()?.collect::<Vec<Vec<u64>>>();
+
+    let mut distinct = guesses.clone();
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(distinct.len(), guesses.len());
+
+    // both dials share the same modulus (100), so the full reachable space
+    // is exactly 100 * 100 distinct guesses, not (2*60+1)^2.
+    assert_eq!(guesses.len(), 100 * 100);
+
+    let errors = guesses.iter().map(|guess| config.errors(guess)).collect::<Vec<u64>>();
+    let mut sorted = errors.clone();
+    sorted.sort();
+    assert_eq!(errors, sorted);
+
+    Ok(())
+  }
+
+  #[test]
+  fn mismatched_range_count_is_an_error() {
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![1, 2],
+      csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Modular,
+      sample: None,
+      seed: 0,
+      combination: vec![50, 50, 50],
+    };
+
+    assert!(config.guesses().is_err());
+  }
+
+  #[test]
+  fn linear_metric_does_not_wrap() -> Result<(), Box<dyn Error>> {
+    let combination = vec![0];
+
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![2],
+      csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Linear,
+      sample: None,
+      seed: 0,
+      combination,
+    };
+
+    // a modular dial would also reach 98 and 99; a linear one stops at 0.
+    let values = config.guesses()?.map(|guess| guess[0]).collect::<Vec<u64>>();
+
+    assert_eq!(values, &[0, 1, 2]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn modular_metric_wraps_past_half_the_modulus() -> Result<(), Box<dyn Error>> {
+    // a `--range` larger than half the modulus must still cost every
+    // candidate by its true wraparound distance, and must not revisit the
+    // same value twice under a different, smaller-looking offset.
+    let combination = vec![20];
+
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![60],
+      csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Modular,
+      sample: None,
+      seed: 0,
+      combination,
+    };
+
+    let guesses = config.guesses()?.collect::<Vec<Vec<u64>>>();
+
+    // the whole 100-value dial is reachable, each value exactly once.
+    assert_eq!(guesses.len(), 100);
+
+    let mut distinct = guesses.clone();
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(distinct.len(), guesses.len());
+
+    let errors = guesses.iter().map(|guess| config.errors(guess)).collect::<Vec<u64>>();
+    let mut sorted = errors.clone();
+    sorted.sort();
+    assert_eq!(errors, sorted);
+
+    // the farthest a modular dial of modulus 100 can be from 20 is 50, at
+    // the antipode (70), never more.
+    assert_eq!(*errors.iter().max().unwrap(), 50);
+
+    Ok(())
+  }
+
+  #[test]
+  fn linear_metric_errors_do_not_wrap() {
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![2],
+      csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Linear,
+      sample: None,
+      seed: 0,
+      combination: vec![0],
+    };
+
+    assert_eq!(config.errors(&[99]), 99);
+  }
+
+  #[test]
+  fn sample_is_distinct_and_sorted_by_error() -> Result<(), Box<dyn Error>> {
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![3],
+      csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Modular,
+      sample: Some(10),
+      seed: 0,
+      combination: vec![50, 50],
+    };
+
+    let guesses = config.sample(10)?;
+
+    assert_eq!(guesses.len(), 10);
+
+    let mut distinct = guesses.clone();
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(distinct.len(), guesses.len());
+
+    let errors = guesses.iter().map(|guess| config.errors(guess)).collect::<Vec<u64>>();
+    let mut sorted = errors.clone();
+    sorted.sort();
+    assert_eq!(errors, sorted);
+
+    Ok(())
+  }
+
+  #[test]
+  fn sample_is_reproducible_given_a_seed() -> Result<(), Box<dyn Error>> {
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![3],
+      csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Modular,
+      sample: Some(10),
+      seed: 42,
+      combination: vec![50, 50],
+    };
+
+    assert_eq!(config.sample(10)?, config.sample(10)?);
+
+    Ok(())
+  }
+
+  #[test]
+  fn sample_caps_at_reachable_space() -> Result<(), Box<dyn Error>> {
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![1],
+      csv: false,
+      json: false,
+      top: None,
+      metric: Metric::Modular,
+      sample: Some(100),
+      seed: 0,
+      combination: vec![50],
+    };
+
+    // only 2*1+1 = 3 reachable values exist for a single dial.
+    assert_eq!(config.sample(100)?.len(), 3);
+
+    Ok(())
+  }
+
+  #[test]
+  fn format_json_record() {
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![2],
+      csv: false,
+      json: true,
+      top: None,
+      metric: Metric::Modular,
+      sample: None,
+      seed: 0,
+      combination: vec![1, 2],
+    };
+
+    assert_eq!(config.format_json(&[0, 3]), r#"{"numbers":[0,3],"errors":2}"#);
+  }
+
+  #[test]
+  fn json_takes_precedence_over_csv() {
+    let config = Config {
+      min: 0,
+      max: 99,
+      range: vec![2],
+      csv: true,
+      json: true,
+      top: None,
+      metric: Metric::Modular,
+      sample: None,
+      seed: 0,
+      combination: vec![1, 2],
+    };
+
+    assert!(matches!(config.output_format(), OutputFormat::Json));
+  }
+
   #[test]
   fn modular_distance_misc() {
     assert_eq!(modular_distance(0, 1, 10), 1);